@@ -41,7 +41,9 @@ impl<'a> std::future::Future for ConnectFuture<'a> {
 			waker.wake_by_ref();
 		})));
 		let server = self.server.take();
-		let flags = self.flags;
+		// `FlagSet` isn't `Copy`/`Clone`; rebuild an owned value from its
+		// bits instead of trying to move it out of `self` behind the `Pin`.
+		let flags = FlagSet::from_bits_truncate(self.flags.bits());
 		let api = self.api.take();
 		if let Err(err) = self.context.connect(server, flags, api) {
 			return Poll::Ready(Err(err));
@@ -363,6 +365,25 @@ impl Context {
 		self.0.get_tile_size(ss)
 	}
 
+	/// Gets an introspection object linked to this context, giving access
+	/// to PulseAudio's server-state query routines.
+	pub fn introspect(&self) -> crate::introspect::Introspector {
+		self.0.introspect().into()
+	}
+
+	/// Gives access to the wrapped `libpulse-binding` context, for use by
+	/// other modules in this crate that extend `Context` with additional
+	/// functionality.
+	pub(crate) fn raw(&self) -> &pulse::context::Context {
+		&self.0
+	}
+
+	/// Mutable counterpart to [`raw`][Self::raw], needed by APIs (such as
+	/// stream creation) that take `&mut pulse::context::Context`.
+	pub(crate) fn raw_mut(&mut self) -> &mut pulse::context::Context {
+		&mut self.0
+	}
+
 	// TODO: load_cookie_from file
 	// TODO: rttime_new
 }
@@ -0,0 +1,190 @@
+//! Async wrapper for the `device-manager` context extension, used to read
+//! and rename the "virtual" devices PulseAudio groups sinks/sources under,
+//! and to control role-based device-priority routing.
+
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
+use libpulse_binding::{
+	callbacks::ListResult,
+	context::ext_device_manager::{self, Info as DeviceManagerInfo, RolePriorityInfo},
+	error::{Code, PAErr},
+};
+
+use crate::{
+	context::Context,
+	list::{self, ListStream},
+	operation::Operation,
+};
+
+/// Deep-copied, owned version of [`RolePriorityInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedRolePriorityInfo {
+	/// Role name.
+	pub role: Option<String>,
+	/// Priority.
+	pub priority: u32,
+}
+
+impl From<&RolePriorityInfo<'_>> for OwnedRolePriorityInfo {
+	fn from(info: &RolePriorityInfo) -> Self {
+		OwnedRolePriorityInfo {
+			role: info.role.as_ref().map(|s| s.to_string()),
+			priority: info.priority,
+		}
+	}
+}
+
+/// Deep-copied, owned version of [`DeviceManagerInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedDeviceInfo {
+	/// Identifier of this device.
+	pub name: Option<String>,
+	/// Human-readable description of this device.
+	pub description: Option<String>,
+	/// Icon name associated with this device, if any.
+	pub icon: Option<String>,
+	/// Index of this device, if it is currently available.
+	pub index: Option<u32>,
+	/// Role priorities assigned to this device.
+	pub role_priorities: Vec<OwnedRolePriorityInfo>,
+}
+
+impl From<&DeviceManagerInfo<'_>> for OwnedDeviceInfo {
+	fn from(info: &DeviceManagerInfo) -> Self {
+		OwnedDeviceInfo {
+			name: info.name.as_ref().map(|s| s.to_string()),
+			description: info.description.as_ref().map(|s| s.to_string()),
+			icon: info.icon.as_ref().map(|s| s.to_string()),
+			index: info.index,
+			role_priorities: info.role_priorities.iter().map(OwnedRolePriorityInfo::from).collect(),
+		}
+	}
+}
+
+/// Return type of [`DeviceManager::read`].
+type DeviceInfoList = ListStream<OwnedDeviceInfo, dyn FnMut(ListResult<&DeviceManagerInfo>)>;
+
+/// Async wrapper around the `device-manager` extension.
+///
+/// Obtained through [`Context::device_manager`].
+pub struct DeviceManager(ext_device_manager::DeviceManager);
+
+impl From<ext_device_manager::DeviceManager> for DeviceManager {
+	fn from(inner: ext_device_manager::DeviceManager) -> Self {
+		DeviceManager(inner)
+	}
+}
+
+impl Context {
+	/// Gets a `device-manager` extension object linked to this context.
+	pub fn device_manager(&self) -> DeviceManager {
+		self.raw().device_manager().into()
+	}
+}
+
+impl DeviceManager {
+	/// Returns a stream of every known device.
+	pub fn read(&mut self) -> DeviceInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.read(move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedDeviceInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Sets the description of the named device.
+	pub async fn set_device_description(&mut self, device: &str, description: &str) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0
+				.set_device_description(device, description, move |suc| {
+					success.store(suc, Ordering::Release)
+				})
+				.into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Deletes the named devices.
+	pub async fn delete(&mut self, devices: &[&str]) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0.delete(devices, move |suc| success.store(suc, Ordering::Release)).into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Enables the role-based device-priority routing mode.
+	pub async fn enable_role_device_priority_routing(&mut self, enable: bool) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0
+				.enable_role_device_priority_routing(enable, move |suc| success.store(suc, Ordering::Release))
+				.into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Reorders the devices preferred for `role`, highest priority first.
+	pub async fn reorder_devices_for_role(&mut self, role: &str, devices: &[&str]) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0
+				.reorder_devices_for_role(role, devices, move |suc| success.store(suc, Ordering::Release))
+				.into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Subscribes to changes in the device-manager database.
+	pub async fn subscribe(&mut self, enable: bool) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0.subscribe(enable, move |suc| success.store(suc, Ordering::Release)).into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Sets the callback invoked whenever the device-manager database
+	/// changes, after a successful [`subscribe`][Self::subscribe] call.
+	pub fn set_subscribe_cb(&mut self, callback: impl FnMut() + 'static) {
+		self.0.set_subscribe_cb(callback);
+	}
+}
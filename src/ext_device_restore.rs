@@ -0,0 +1,145 @@
+//! Async wrapper for the `device-restore` context extension, used to read
+//! and persist the format state PulseAudio restores sinks and sources to on
+//! startup.
+
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
+use libpulse_binding::{
+	callbacks::ListResult,
+	context::ext_device_restore::{self, Info as DeviceInfo},
+	def::Device,
+	error::{Code, PAErr},
+	format,
+};
+
+use crate::{
+	context::Context,
+	list::{self, ListStream},
+	operation::Operation,
+};
+
+/// Deep-copied, owned version of [`DeviceInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedDeviceInfo {
+	/// Whether this entry describes a sink or a source.
+	pub device_type: Device,
+	/// Index of the device this entry applies to.
+	pub index: u32,
+	/// Formats this device has been configured to use, in priority order.
+	pub formats: Vec<format::Info>,
+}
+
+impl From<&DeviceInfo> for OwnedDeviceInfo {
+	fn from(info: &DeviceInfo) -> Self {
+		OwnedDeviceInfo {
+			device_type: info.dtype,
+			index: info.index,
+			formats: info.formats.clone(),
+		}
+	}
+}
+
+/// Return type of [`DeviceRestore::read_formats_all`]/[`DeviceRestore::read_formats`].
+type DeviceInfoList = ListStream<OwnedDeviceInfo, dyn FnMut(ListResult<&DeviceInfo>)>;
+
+/// Async wrapper around the `device-restore` extension.
+///
+/// Obtained through [`Context::device_restore`].
+pub struct DeviceRestore(ext_device_restore::DeviceRestore);
+
+impl From<ext_device_restore::DeviceRestore> for DeviceRestore {
+	fn from(inner: ext_device_restore::DeviceRestore) -> Self {
+		DeviceRestore(inner)
+	}
+}
+
+impl Context {
+	/// Gets a `device-restore` extension object linked to this context.
+	pub fn device_restore(&self) -> DeviceRestore {
+		self.raw().device_restore().into()
+	}
+}
+
+impl DeviceRestore {
+	/// Returns a stream of the saved formats for every present device.
+	pub fn read_formats_all(&mut self) -> DeviceInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.read_formats_all(move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedDeviceInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Returns a stream of the saved formats for the device at `index`.
+	pub fn read_formats(&mut self, device_type: Device, index: u32) -> DeviceInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.read_formats(device_type, index, move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedDeviceInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Saves the preferred formats for the device at `index`.
+	pub async fn save_formats(
+		&mut self,
+		device_type: Device,
+		index: u32,
+		formats: &mut [&mut format::Info],
+	) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0
+				.save_formats(device_type, index, formats, move |suc| {
+					success.store(suc, Ordering::Release)
+				})
+				.into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Subscribes to changes in the device-restore database.
+	pub async fn subscribe(&mut self, enable: bool) -> Result<(), PAErr> {
+		let success = Arc::new(AtomicBool::new(false));
+		let op: Operation<_> = {
+			let success = Arc::clone(&success);
+			self.0.subscribe(enable, move |suc| success.store(suc, Ordering::Release)).into()
+		};
+		op.await?;
+		match success.load(Ordering::Acquire) {
+			false => Err(Code::Unknown.into()),
+			true => Ok(()),
+		}
+	}
+
+	/// Sets the callback invoked whenever the device-restore database
+	/// changes, after a successful [`subscribe`][Self::subscribe] call.
+	pub fn set_subscribe_cb(&mut self, callback: impl FnMut(Device, u32) + 'static) {
+		self.0.set_subscribe_cb(callback);
+	}
+}
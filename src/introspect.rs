@@ -0,0 +1,391 @@
+//! Async introspection: enumerate and query sinks, sources and clients
+//! without handling PulseAudio's list callbacks directly.
+
+use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+
+use libpulse_binding::{
+	callbacks::ListResult,
+	channelmap::Map as ChannelMap,
+	context::introspect::{self, ClientInfo, SinkInfo, SinkPortInfo, SourceInfo, SourcePortInfo},
+	def::{PortAvailable, SinkFlagSet, SourceFlagSet},
+	error::{Code, PAErr},
+	format,
+	proplist::Proplist,
+	sample::Spec,
+	time::MicroSeconds,
+	volume::ChannelVolumes,
+};
+
+use crate::{
+	list::{self, ListStream},
+	operation::Operation,
+};
+
+/// Return types of the list-query methods below, one per info type: each is
+/// a [`ListStream`] fed by the matching `ListResult<&Raw>` callback.
+type SinkInfoList = ListStream<OwnedSinkInfo, dyn FnMut(ListResult<&SinkInfo>)>;
+type SourceInfoList = ListStream<OwnedSourceInfo, dyn FnMut(ListResult<&SourceInfo>)>;
+type ClientInfoList = ListStream<OwnedClientInfo, dyn FnMut(ListResult<&ClientInfo>)>;
+
+/// Deep-copied, owned version of [`SinkPortInfo`]/[`SourcePortInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedPortInfo {
+	/// Name of this port.
+	pub name: Option<String>,
+	/// Description of this port.
+	pub description: Option<String>,
+	/// The higher this value is, the more useful this port is as a default.
+	pub priority: u32,
+	/// Availability status of this port.
+	pub available: PortAvailable,
+}
+
+impl From<&SinkPortInfo<'_>> for OwnedPortInfo {
+	fn from(info: &SinkPortInfo) -> Self {
+		OwnedPortInfo {
+			name: info.name.as_ref().map(|s| s.to_string()),
+			description: info.description.as_ref().map(|s| s.to_string()),
+			priority: info.priority,
+			available: info.available,
+		}
+	}
+}
+
+impl From<&SourcePortInfo<'_>> for OwnedPortInfo {
+	fn from(info: &SourcePortInfo) -> Self {
+		OwnedPortInfo {
+			name: info.name.as_ref().map(|s| s.to_string()),
+			description: info.description.as_ref().map(|s| s.to_string()),
+			priority: info.priority,
+			available: info.available,
+		}
+	}
+}
+
+/// Deep-copied, owned version of [`SinkInfo`], safe to hold on to past the
+/// lifetime of the callback PulseAudio invoked it in.
+#[derive(Debug, Clone)]
+pub struct OwnedSinkInfo {
+	/// Name of the sink.
+	pub name: Option<String>,
+	/// Index of the sink.
+	pub index: u32,
+	/// Description of the sink.
+	pub description: Option<String>,
+	/// Sample spec of the sink.
+	pub sample_spec: Spec,
+	/// Channel map of the sink.
+	pub channel_map: ChannelMap,
+	/// Volume of the sink.
+	pub volume: ChannelVolumes,
+	/// Whether the sink is muted.
+	pub mute: bool,
+	/// Index of the monitor source connected to this sink.
+	pub monitor_source: u32,
+	/// Name of the monitor source connected to this sink.
+	pub monitor_source_name: Option<String>,
+	/// Length of queued audio in the output buffer.
+	pub latency: MicroSeconds,
+	/// Driver name.
+	pub driver: Option<String>,
+	/// Flags.
+	pub flags: SinkFlagSet,
+	/// Set of available ports.
+	pub ports: Vec<OwnedPortInfo>,
+	/// Set of formats supported by the sink.
+	pub formats: Vec<format::Info>,
+	/// Index of the card this sink belongs to, if any.
+	pub card: Option<u32>,
+	/// Property list of the sink.
+	pub proplist: Proplist,
+}
+
+impl From<&SinkInfo<'_>> for OwnedSinkInfo {
+	fn from(info: &SinkInfo) -> Self {
+		OwnedSinkInfo {
+			name: info.name.as_ref().map(|s| s.to_string()),
+			index: info.index,
+			description: info.description.as_ref().map(|s| s.to_string()),
+			sample_spec: info.sample_spec,
+			channel_map: info.channel_map,
+			volume: info.volume,
+			mute: info.mute,
+			monitor_source: info.monitor_source,
+			monitor_source_name: info.monitor_source_name.as_ref().map(|s| s.to_string()),
+			latency: info.latency,
+			driver: info.driver.as_ref().map(|s| s.to_string()),
+			flags: info.flags,
+			ports: info.ports.iter().map(OwnedPortInfo::from).collect(),
+			formats: info.formats.clone(),
+			card: info.card,
+			proplist: info.proplist.clone(),
+		}
+	}
+}
+
+/// Deep-copied, owned version of [`SourceInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedSourceInfo {
+	/// Name of the source.
+	pub name: Option<String>,
+	/// Index of the source.
+	pub index: u32,
+	/// Description of the source.
+	pub description: Option<String>,
+	/// Sample spec of the source.
+	pub sample_spec: Spec,
+	/// Channel map of the source.
+	pub channel_map: ChannelMap,
+	/// Volume of the source.
+	pub volume: ChannelVolumes,
+	/// Whether the source is muted.
+	pub mute: bool,
+	/// If this is a monitor source, the index of the owning sink.
+	pub monitor_of_sink: Option<u32>,
+	/// Name of the owning sink, if this is a monitor source.
+	pub monitor_of_sink_name: Option<String>,
+	/// Length of filled record buffer of this source.
+	pub latency: MicroSeconds,
+	/// Driver name.
+	pub driver: Option<String>,
+	/// Flags.
+	pub flags: SourceFlagSet,
+	/// Set of available ports.
+	pub ports: Vec<OwnedPortInfo>,
+	/// Set of formats supported by the source.
+	pub formats: Vec<format::Info>,
+	/// Index of the card this source belongs to, if any.
+	pub card: Option<u32>,
+	/// Property list of the source.
+	pub proplist: Proplist,
+}
+
+impl From<&SourceInfo<'_>> for OwnedSourceInfo {
+	fn from(info: &SourceInfo) -> Self {
+		OwnedSourceInfo {
+			name: info.name.as_ref().map(|s| s.to_string()),
+			index: info.index,
+			description: info.description.as_ref().map(|s| s.to_string()),
+			sample_spec: info.sample_spec,
+			channel_map: info.channel_map,
+			volume: info.volume,
+			mute: info.mute,
+			monitor_of_sink: info.monitor_of_sink,
+			monitor_of_sink_name: info.monitor_of_sink_name.as_ref().map(|s| s.to_string()),
+			latency: info.latency,
+			driver: info.driver.as_ref().map(|s| s.to_string()),
+			flags: info.flags,
+			ports: info.ports.iter().map(OwnedPortInfo::from).collect(),
+			formats: info.formats.clone(),
+			card: info.card,
+			proplist: info.proplist.clone(),
+		}
+	}
+}
+
+/// Deep-copied, owned version of [`ClientInfo`].
+#[derive(Debug, Clone)]
+pub struct OwnedClientInfo {
+	/// Index of this client.
+	pub index: u32,
+	/// Name of this client.
+	pub name: Option<String>,
+	/// Index of the owning module, if any.
+	pub owner_module: Option<u32>,
+	/// Driver name this client was created with.
+	pub driver: Option<String>,
+	/// Property list of the client.
+	pub proplist: Proplist,
+}
+
+impl From<&ClientInfo<'_>> for OwnedClientInfo {
+	fn from(info: &ClientInfo) -> Self {
+		OwnedClientInfo {
+			index: info.index,
+			name: info.name.as_ref().map(|s| s.to_string()),
+			owner_module: info.owner_module,
+			driver: info.driver.as_ref().map(|s| s.to_string()),
+			proplist: info.proplist.clone(),
+		}
+	}
+}
+
+/// Async wrapper around PulseAudio's introspection routines.
+///
+/// Obtained through [`Context::introspect`][crate::context::Context::introspect].
+pub struct Introspector(introspect::Introspector);
+
+impl From<introspect::Introspector> for Introspector {
+	fn from(inner: introspect::Introspector) -> Self {
+		Introspector(inner)
+	}
+}
+
+/// Awaits the single item sent by a lookup callback, which PulseAudio
+/// invokes with at most one `Item` followed by `End` or `Error`, keeping
+/// `operation` alive until it resolves.
+async fn resolve_one<T, F: ?Sized>(
+	mut receiver: UnboundedReceiver<Result<T, PAErr>>,
+	operation: Operation<F>,
+) -> Result<T, PAErr> {
+	let result = receiver.next().await.unwrap_or(Err(Code::NoEntity.into()));
+	drop(operation);
+	result
+}
+
+impl Introspector {
+	/// Returns a stream of all sinks currently loaded on the server.
+	///
+	/// PulseAudio's `ListResult::Error` carries no error code of its own,
+	/// so a failed enumeration is reported as [`Code::Unknown`]; callers
+	/// that need the precise failure should inspect
+	/// [`Context::get_state`][crate::context::Context::get_state] afterwards.
+	pub fn get_sink_info_list(&self) -> SinkInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_sink_info_list(move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedSinkInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Returns a stream of all sources currently loaded on the server.
+	pub fn get_source_info_list(&self) -> SourceInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_source_info_list(move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedSourceInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Returns a stream of all clients currently connected to the server.
+	pub fn get_client_info_list(&self) -> ClientInfoList {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_client_info_list(move |result| match result {
+				ListResult::Item(info) => {
+					let _ = tx.unbounded_send(Ok(OwnedClientInfo::from(info)));
+				}
+				ListResult::End => (),
+				ListResult::Error => {
+					let _ = tx.unbounded_send(Err(Code::Unknown.into()));
+				}
+			})
+			.into();
+		ListStream::new(rx, op)
+	}
+
+	/// Gets information about the sink with the given name.
+	pub async fn get_sink_info_by_name(&self, name: &str) -> Result<OwnedSinkInfo, PAErr> {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_sink_info_by_name(name, move |result| {
+				if let ListResult::Item(info) = result {
+					let _ = tx.unbounded_send(Ok(OwnedSinkInfo::from(info)));
+				}
+			})
+			.into();
+		resolve_one(rx, op).await
+	}
+
+	/// Gets information about the source with the given name.
+	pub async fn get_source_info_by_name(&self, name: &str) -> Result<OwnedSourceInfo, PAErr> {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_source_info_by_name(name, move |result| {
+				if let ListResult::Item(info) = result {
+					let _ = tx.unbounded_send(Ok(OwnedSourceInfo::from(info)));
+				}
+			})
+			.into();
+		resolve_one(rx, op).await
+	}
+
+	/// Gets information about the client with the given index.
+	pub async fn get_client_info(&self, index: u32) -> Result<OwnedClientInfo, PAErr> {
+		let (tx, rx) = list::channel();
+		let op = self
+			.0
+			.get_client_info(index, move |result| {
+				if let ListResult::Item(info) = result {
+					let _ = tx.unbounded_send(Ok(OwnedClientInfo::from(info)));
+				}
+			})
+			.into();
+		resolve_one(rx, op).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::borrow::Cow;
+
+	use libpulse_binding::{
+		def::SinkState,
+		sample::Format,
+		volume::Volume,
+	};
+
+	use super::*;
+
+	#[test]
+	fn owned_sink_info_deep_copies_borrowed_strings() {
+		let name = String::from("alsa_output.pci-0000_00_1f.3.analog-stereo");
+		let info = SinkInfo {
+			name: Some(Cow::Borrowed(name.as_str())),
+			index: 0,
+			description: None,
+			sample_spec: Spec { format: Format::S16le, rate: 44100, channels: 2 },
+			channel_map: ChannelMap::default(),
+			owner_module: None,
+			volume: ChannelVolumes::default(),
+			mute: false,
+			monitor_source: 0,
+			monitor_source_name: None,
+			latency: MicroSeconds(0),
+			driver: None,
+			flags: SinkFlagSet::empty(),
+			proplist: Proplist::new().unwrap(),
+			configured_latency: MicroSeconds(0),
+			base_volume: Volume(0),
+			state: SinkState::Running,
+			n_volume_steps: 0,
+			card: None,
+			ports: Vec::new(),
+			active_port: None,
+			formats: Vec::new(),
+		};
+
+		let owned = OwnedSinkInfo::from(&info);
+		// Drop the borrowed `SinkInfo` and the string it pointed into: if
+		// `OwnedSinkInfo` had kept a borrow instead of copying, this would
+		// be a use-after-free rather than the stale-but-valid owned
+		// `String` it's meant to produce.
+		drop(info);
+		drop(name);
+
+		assert_eq!(owned.name.as_deref(), Some("alsa_output.pci-0000_00_1f.3.analog-stereo"));
+		assert!(owned.ports.is_empty());
+		assert!(owned.formats.is_empty());
+	}
+}
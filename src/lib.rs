@@ -16,5 +16,11 @@
 extern crate libpulse_binding as pulse;
 
 pub mod context;
-//pub mod mainloop;
+pub mod ext_device_manager;
+pub mod ext_device_restore;
+pub mod ext_stream_restore;
+pub mod introspect;
+mod list;
+pub mod mainloop;
 pub mod operation;
+pub mod stream;
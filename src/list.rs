@@ -0,0 +1,51 @@
+//! Shared machinery for turning PulseAudio's list-style callbacks (zero or
+//! more `Item`s followed by one `End`/`Error`) into a `Stream`.
+
+use std::{
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use futures::{
+	channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+	Stream,
+};
+use libpulse_binding::error::PAErr;
+
+use crate::operation::Operation;
+
+/// The sender/receiver pair returned by [`channel`].
+type Channel<T> = (UnboundedSender<Result<T, PAErr>>, UnboundedReceiver<Result<T, PAErr>>);
+
+/// A `Stream` of the items produced by one of PulseAudio's list-style
+/// introspection calls.
+///
+/// Holds the [`Operation`] that's feeding it alive for as long as the
+/// stream is, since dropping the operation early would cancel the
+/// in-flight enumeration on the server.
+pub struct ListStream<T, F: ?Sized> {
+	items: UnboundedReceiver<Result<T, PAErr>>,
+	_operation: Operation<F>,
+}
+
+impl<T, F: ?Sized> ListStream<T, F> {
+	pub(crate) fn new(items: UnboundedReceiver<Result<T, PAErr>>, operation: Operation<F>) -> Self {
+		ListStream { items, _operation: operation }
+	}
+}
+
+impl<T, F: ?Sized> Stream for ListStream<T, F> {
+	type Item = Result<T, PAErr>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		Pin::new(&mut self.items).poll_next(cx)
+	}
+}
+
+/// Creates the sender/receiver pair a list callback bridges into a
+/// [`ListStream`] through: the callback pushes one `Ok` per `Item`, and an
+/// `Err` followed by closing the sender on `Error`; closing the sender
+/// without an `Err` (on `End`) ends the stream cleanly.
+pub(crate) fn channel<T>() -> Channel<T> {
+	mpsc::unbounded()
+}
@@ -0,0 +1,601 @@
+//! A `Mainloop` implementation that drives PulseAudio's event sources
+//! through an external async reactor instead of a dedicated PulseAudio
+//! thread.
+//!
+//! PulseAudio expects the application to hand it a [`MainloopApi`], a table
+//! of callbacks it uses to ask its host to watch file descriptors, arm
+//! timers and run deferred work. The stock `libpulse-binding` mainloops
+//! (`standard`, `threaded`) implement that table on top of their own C
+//! event loop, which means they either block the calling thread or spin up
+//! a dedicated one. This module implements the table on top of
+//! [`async-io`]'s reactor; the tasks serving the events PulseAudio asks for
+//! run on a single background executor thread this module starts (see
+//! [`executor_sender`]), not on the executor driving the rest of the
+//! application.
+
+use std::{
+	collections::HashMap,
+	ffi::c_void,
+	os::{
+		fd::{AsFd, BorrowedFd as StdBorrowedFd},
+		unix::io::RawFd,
+	},
+	rc::Rc,
+	sync::{mpsc, Arc, Mutex, OnceLock},
+	thread,
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_io::{Async, Timer};
+use async_task::{Runnable, Task};
+use libc::timeval;
+
+use libpulse_binding::{
+	def::RetvalActual,
+	mainloop::{
+		api::{
+			DeferEventCb, IoEventCb, MainloopApi, MainloopInner, MainloopInnerType,
+			MainloopInternalType, TimeEventCb,
+		},
+		events::{
+			deferred::DeferEventInternal,
+			io::{FlagSet as IoEventFlags, IoEventInternal},
+			timer::TimeEventInternal,
+		},
+	},
+};
+
+type SourceId = usize;
+
+/// A file descriptor PulseAudio has asked us to watch, together with the
+/// task currently waiting on it.
+struct IoSource {
+	fd: RawFd,
+	flags: IoEventFlags,
+	cb: IoEventCb,
+	userdata: *mut c_void,
+	task: Option<Task<()>>,
+}
+
+struct TimeSource {
+	cb: TimeEventCb,
+	userdata: *mut c_void,
+	task: Option<Task<()>>,
+}
+
+struct DeferSource {
+	cb: DeferEventCb,
+	userdata: *mut c_void,
+	enabled: bool,
+	task: Option<Task<()>>,
+}
+
+/// Shared state backing a [`Mainloop`].
+///
+/// Event sources are keyed by a monotonically increasing id; the
+/// `*_new` functions hand back a boxed [`IoHandle`]/[`TimeHandle`]/
+/// [`DeferHandle`] (cast to the opaque `*_event` pointer type PulseAudio
+/// expects) that carries the id and a registry handle, since
+/// `io_enable`/`io_free`/`time_restart`/`time_free`/`defer_enable`/
+/// `defer_free` only ever receive that opaque pointer back, never the
+/// `userdata` the mainloop API itself was constructed with.
+#[derive(Default)]
+struct Inner {
+	io: HashMap<SourceId, IoSource>,
+	time: HashMap<SourceId, TimeSource>,
+	defer: HashMap<SourceId, DeferSource>,
+	next_id: SourceId,
+}
+
+// SAFETY: the `*mut c_void` userdata pointers stashed in `IoSource`/
+// `TimeSource`/`DeferSource` are opaque tags PulseAudio gave us; `Inner`
+// itself never dereferences them, it only ever hands them back to the `cb`
+// function pointer stored alongside them, and all access to `Inner` is
+// already serialized through the `Mutex` wrapping it.
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl Inner {
+	fn fresh_id(&mut self) -> SourceId {
+		self.next_id += 1;
+		self.next_id
+	}
+}
+
+/// Wraps a raw pointer PulseAudio gave us (an opaque `*_event` handle) so it
+/// can be captured by the `Send` futures spawned below. We never
+/// dereference it ourselves; it's only ever handed back to the `cb`
+/// function pointer that does, exactly as PulseAudio itself would call it.
+struct Opaque<T>(*mut T);
+
+// SAFETY: see `Inner`, above; this is the same "opaque tag, never
+// dereferenced locally" reasoning.
+unsafe impl<T> Send for Opaque<T> {}
+
+impl<T> Opaque<T> {
+	/// Reads out the wrapped pointer through a method rather than the tuple
+	/// field directly, so that capturing `self.get()` inside an `async move`
+	/// block captures the whole (`Send`) `Opaque`, not just the (`!Send`)
+	/// raw pointer field — the latter is what Rust's precise closure/async
+	/// capture would otherwise pull in on its own.
+	fn get(&self) -> *mut T {
+		self.0
+	}
+}
+
+/// An fd PulseAudio owns the lifetime of. `async-io` normally closes the fd
+/// it wraps on drop, which would be wrong here: PulseAudio opened it and
+/// remains responsible for closing it via its own cleanup, not us.
+struct BorrowedFd(RawFd);
+
+impl AsFd for BorrowedFd {
+	fn as_fd(&self) -> StdBorrowedFd<'_> {
+		// SAFETY: PulseAudio keeps `self.0` open for as long as the `IoSource`
+		// naming it exists, which outlives every `BorrowedFd` built from it.
+		unsafe { StdBorrowedFd::borrow_raw(self.0) }
+	}
+}
+
+/// An async mainloop backed by [`async-io`]'s reactor.
+///
+/// Construct one of these and pass it to
+/// [`Context::new`][crate::context::Context::new] in place of the
+/// `standard`/`threaded` mainloops from `libpulse-binding`. Unlike those,
+/// `Mainloop` doesn't need to be run or iterated explicitly: PulseAudio's
+/// event sources are serviced in the background by this crate's own
+/// executor thread (see [`executor_sender`]).
+pub struct Mainloop {
+	_inner: Rc<MainloopInner<MainloopTag>>,
+}
+
+impl Default for Mainloop {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Mainloop {
+	/// Creates a new, empty mainloop. No event sources are registered until
+	/// PulseAudio asks for them, which happens once a
+	/// [`Context`][crate::context::Context] built on this mainloop starts
+	/// connecting.
+	pub fn new() -> Self {
+		let registry = Arc::new(Mutex::new(Inner::default()));
+		let userdata = Arc::into_raw(registry) as *mut c_void;
+		let api = Box::new(MainloopApi {
+			userdata,
+			io_new: Some(raw::io_new),
+			io_enable: Some(raw::io_enable),
+			io_free: Some(raw::io_free),
+			io_set_destroy: None,
+			time_new: Some(raw::time_new),
+			time_restart: Some(raw::time_restart),
+			time_free: Some(raw::time_free),
+			time_set_destroy: None,
+			defer_new: Some(raw::defer_new),
+			defer_enable: Some(raw::defer_enable),
+			defer_free: Some(raw::defer_free),
+			defer_set_destroy: None,
+			quit: Some(raw::quit),
+		});
+		let api_ptr: *const MainloopApi = Box::leak(api);
+		// SAFETY: `api_ptr` was just leaked above and is never freed except
+		// by `drop_mainloop_inner`, below, which is the `dropfn` this call
+		// registers.
+		let ml_inner = unsafe {
+			MainloopInner::<MainloopTag>::new(std::ptr::null_mut(), api_ptr, drop_mainloop_inner, false)
+		};
+		Mainloop { _inner: Rc::new(ml_inner) }
+	}
+}
+
+/// Marker type used to identify this mainloop's `MainloopInner` instance.
+/// We have no real opaque C mainloop object of our own, so unlike
+/// `standard`/`threaded` this type is never actually pointed to by anything.
+pub struct MainloopTag;
+
+impl MainloopInternalType for MainloopTag {}
+
+/// Frees the boxed [`MainloopApi`] leaked in [`Mainloop::new`] and releases
+/// the strong reference to the event registry stashed in its `userdata`
+/// field. `MainloopInner` is a foreign type, so this has to be a free
+/// function (an inherent impl here would violate the orphan rules) matching
+/// the `dropfn` signature `MainloopInnerType::new` expects.
+fn drop_mainloop_inner(inner: &mut MainloopInner<MainloopTag>) {
+	// SAFETY: `get_api_ptr()` returns the exact pointer `Mainloop::new`
+	// leaked via `Box::leak`, and this is the only place it's ever
+	// reclaimed.
+	unsafe {
+		let api = Box::from_raw(inner.get_api_ptr() as *mut MainloopApi);
+		drop(Arc::from_raw(api.userdata as *const Mutex<Inner>));
+	}
+}
+
+impl libpulse_binding::mainloop::api::Mainloop for Mainloop {
+	type MI = MainloopInner<MainloopTag>;
+
+	fn inner(&self) -> Rc<Self::MI> {
+		Rc::clone(&self._inner)
+	}
+}
+
+/// Converts an absolute Unix `timeval` (as passed to `time_new`/
+/// `time_restart`) to the `Instant` `async-io`'s `Timer` expects, by
+/// measuring its distance from the current wall-clock time. A `timeval`
+/// already in the past fires immediately.
+fn timeval_to_instant(tv: &timeval) -> Instant {
+	let target = UNIX_EPOCH + Duration::new(tv.tv_sec.max(0) as u64, (tv.tv_usec.max(0) as u32) * 1000);
+	match target.duration_since(SystemTime::now()) {
+		Ok(remaining) => Instant::now() + remaining,
+		Err(_) => Instant::now(),
+	}
+}
+
+/// Spawns the task that waits on `id`'s fd becoming ready and invokes the
+/// stored callback each time it is, re-reading the registration on every
+/// iteration so a callback that changes its own interest flags (via
+/// `io_enable`) takes effect on the next wait.
+fn spawn_io_task(registry: &Arc<Mutex<Inner>>, id: SourceId, event: *mut IoEventInternal) {
+	let task_registry = Arc::clone(registry);
+	let event = Opaque(event);
+	let task = async_task_spawn(async move {
+		loop {
+			let (fd, flags) = {
+				let guard = task_registry.lock().unwrap();
+				match guard.io.get(&id) {
+					// `IoEventFlags` isn't `Copy`/`Clone`; rebuild an owned
+					// value from its bits instead of trying to move it out
+					// of the borrowed `source`.
+					Some(source) => (source.fd, IoEventFlags::from_bits_truncate(source.flags.bits())),
+					None => return,
+				}
+			};
+			let Ok(async_fd) = Async::new(BorrowedFd(fd)) else { return };
+			let readable = flags.contains(IoEventFlags::INPUT);
+			let writable = flags.contains(IoEventFlags::OUTPUT);
+			match (readable, writable) {
+				(true, true) => {
+					futures::future::select(async_fd.readable(), async_fd.writable()).await;
+				}
+				(true, false) => {
+					let _ = async_fd.readable().await;
+				}
+				(false, true) => {
+					let _ = async_fd.writable().await;
+				}
+				(false, false) => return,
+			}
+			// Dropping deregisters the fd from the reactor (without closing
+			// it, see `BorrowedFd`), so the next iteration can register it again.
+			drop(async_fd);
+
+			// Re-lock only long enough to read out what's needed to call
+			// back into PulseAudio; the lock must not be held across the
+			// callback itself, since the callback may re-enter us (e.g. to
+			// free or re-enable this very source).
+			let invoked = {
+				let guard = task_registry.lock().unwrap();
+				guard.io.get(&id).map(|source| (source.cb, source.userdata))
+			};
+			let Some((cb, userdata)) = invoked else { return };
+			cb(std::ptr::null(), event.get(), fd, flags, userdata);
+		}
+	});
+
+	if let Some(source) = registry.lock().unwrap().io.get_mut(&id) {
+		source.task = Some(task);
+	}
+}
+
+/// Spawns the task that fires `id`'s timer once, at `at`.
+fn spawn_time_task(registry: &Arc<Mutex<Inner>>, id: SourceId, event: *mut TimeEventInternal, at: Instant) {
+	let task_registry = Arc::clone(registry);
+	let event = Opaque(event);
+	let task = async_task_spawn(async move {
+		Timer::at(at).await;
+		let invoked = {
+			let guard = task_registry.lock().unwrap();
+			guard.time.get(&id).map(|source| (source.cb, source.userdata))
+		};
+		if let Some((cb, userdata)) = invoked {
+			cb(std::ptr::null(), event.get(), std::ptr::null(), userdata);
+		}
+	});
+
+	if let Some(source) = registry.lock().unwrap().time.get_mut(&id) {
+		source.task = Some(task);
+	}
+}
+
+/// Spawns the task backing a deferred event, which PulseAudio expects to
+/// keep firing once per loop iteration until disabled or freed. We
+/// approximate "loop iteration" with a single executor yield between
+/// firings, which keeps a busy deferred event from starving other tasks.
+fn spawn_defer_task(registry: &Arc<Mutex<Inner>>, id: SourceId, event: *mut DeferEventInternal) {
+	let task_registry = Arc::clone(registry);
+	let event = Opaque(event);
+	let task = async_task_spawn(async move {
+		loop {
+			futures::pending!();
+			let invoked = {
+				let guard = task_registry.lock().unwrap();
+				match guard.defer.get(&id) {
+					Some(source) if source.enabled => Some((source.cb, source.userdata)),
+					Some(_) => None,
+					None => return,
+				}
+			};
+			if let Some((cb, userdata)) = invoked {
+				cb(std::ptr::null(), event.get(), userdata);
+			}
+		}
+	});
+
+	if let Some(source) = registry.lock().unwrap().defer.get_mut(&id) {
+		source.task = Some(task);
+	}
+}
+
+/// Detaches `future` onto this process's background executor thread (see
+/// [`executor_sender`]) so it keeps running without anyone holding onto its
+/// `Task`, other than the registry entry used to cancel it on `*_free`.
+fn async_task_spawn(future: impl std::future::Future<Output = ()> + Send + 'static) -> Task<()> {
+	let sender = executor_sender().clone();
+	let schedule = move |runnable: Runnable| {
+		// The receiving end only ever goes away once the whole process is
+		// tearing down (it's owned by a detached thread that outlives every
+		// `Mainloop`), so a send failure here has nowhere useful to go.
+		let _ = sender.send(runnable);
+	};
+	let (runnable, task) = async_task::spawn(future, schedule);
+	runnable.schedule();
+	task
+}
+
+/// Returns the sending half of the queue feeding this process's single
+/// background executor thread, starting that thread the first time it's
+/// needed.
+///
+/// A `schedule` callback can fire from any thread — notably `async-io`'s own
+/// reactor thread, the moment a watched fd becomes ready or a timer elapses
+/// — so running a `Runnable` inline wherever it happens to be woken isn't an
+/// option. Instead every `schedule` call above just hands the `Runnable` to
+/// this dedicated thread, which does nothing but drain the queue and
+/// `run()` whatever it finds: the minimal single-threaded executor pattern
+/// documented at the top of `async-task`'s own crate docs.
+fn executor_sender() -> &'static mpsc::Sender<Runnable> {
+	static SENDER: OnceLock<mpsc::Sender<Runnable>> = OnceLock::new();
+	SENDER.get_or_init(|| {
+		let (sender, receiver) = mpsc::channel::<Runnable>();
+		thread::Builder::new()
+			.name("pulse-async-task".to_owned())
+			.spawn(move || {
+				for runnable in receiver {
+					runnable.run();
+				}
+			})
+			.expect("failed to spawn the pulse-async-task executor thread");
+		sender
+	})
+}
+
+/// The raw `extern "C"` entry points PulseAudio calls through the
+/// `MainloopApi` vtable built in [`Mainloop::new`]. The `*_new` functions
+/// recover the event registry from the `MainloopApi`'s `userdata` field
+/// (which they alone receive); every other function only ever receives
+/// back the opaque event pointer they themselves returned, so they recover
+/// the registry from the boxed handle behind it instead.
+mod raw {
+	use super::*;
+
+	/// A boxed `IoHandle`/`TimeHandle`/`DeferHandle` is what we actually
+	/// hand PulseAudio as the opaque `*_event` pointer: it carries both the
+	/// id used to look the source up in the registry, and the registry
+	/// itself, since `io_enable`/`time_restart`/`defer_enable` and their
+	/// `*_free` counterparts are never passed the mainloop's own `userdata`.
+	struct IoHandle {
+		id: SourceId,
+		registry: Arc<Mutex<Inner>>,
+	}
+
+	struct TimeHandle {
+		id: SourceId,
+		registry: Arc<Mutex<Inner>>,
+	}
+
+	struct DeferHandle {
+		id: SourceId,
+		registry: Arc<Mutex<Inner>>,
+	}
+
+	/// Reconstructs the registry `Arc` stashed in a `MainloopApi`'s
+	/// `userdata` field without taking ownership of the strong reference the
+	/// `MainloopApi` itself is holding on our behalf.
+	///
+	/// # Safety
+	/// `api` must be a pointer to a `MainloopApi` whose `userdata` was
+	/// produced by `Arc::into_raw::<Mutex<Inner>>` in `Mainloop::new`.
+	unsafe fn registry_from_api(api: *const MainloopApi) -> Arc<Mutex<Inner>> {
+		let raw = (*api).userdata as *const Mutex<Inner>;
+		let arc = Arc::from_raw(raw);
+		let clone = Arc::clone(&arc);
+		std::mem::forget(arc);
+		clone
+	}
+
+	pub(super) extern "C" fn io_new(
+		api: *const MainloopApi,
+		fd: RawFd,
+		events: IoEventFlags,
+		cb: Option<IoEventCb>,
+		userdata: *mut c_void,
+	) -> *mut IoEventInternal {
+		let Some(cb) = cb else { return std::ptr::null_mut() };
+		// SAFETY: `api` is the pointer passed down from `MainloopInner`'s
+		// stored `api` field by `libpulse-binding`, which is always valid
+		// for as long as the mainloop it belongs to is.
+		let registry = unsafe { registry_from_api(api) };
+		let id = {
+			let mut guard = registry.lock().unwrap();
+			let id = guard.fresh_id();
+			guard.io.insert(id, IoSource { fd, flags: events, cb, userdata, task: None });
+			id
+		};
+		let handle = Box::into_raw(Box::new(IoHandle { id, registry: Arc::clone(&registry) }));
+		let event = handle as *mut IoEventInternal;
+		spawn_io_task(&registry, id, event);
+		event
+	}
+
+	pub(super) extern "C" fn io_enable(e: *mut IoEventInternal, events: IoEventFlags) {
+		// SAFETY: `e` is a pointer to an `IoHandle` we boxed in `io_new` and
+		// handed back to PulseAudio as the opaque event pointer.
+		let handle = unsafe { &*(e as *const IoHandle) };
+		if let Some(source) = handle.registry.lock().unwrap().io.get_mut(&handle.id) {
+			source.flags = events;
+		}
+		// The running task re-reads `flags` on every wait iteration, so
+		// merely updating it is enough; no need to respawn.
+	}
+
+	pub(super) extern "C" fn io_free(e: *mut IoEventInternal) {
+		// SAFETY: see `io_enable`; this additionally reclaims the box,
+		// which is sound since PulseAudio never uses an event pointer again
+		// after freeing it.
+		let handle = unsafe { Box::from_raw(e as *mut IoHandle) };
+		handle.registry.lock().unwrap().io.remove(&handle.id);
+	}
+
+	pub(super) extern "C" fn time_new(
+		api: *const MainloopApi,
+		tv: *const timeval,
+		cb: Option<TimeEventCb>,
+		userdata: *mut c_void,
+	) -> *mut TimeEventInternal {
+		let Some(cb) = cb else { return std::ptr::null_mut() };
+		// SAFETY: see `io_new`.
+		let registry = unsafe { registry_from_api(api) };
+		// SAFETY: `tv` is the absolute Unix time PulseAudio wants this
+		// timer armed for, always non-null per the `MainloopApi` contract.
+		let at = unsafe { timeval_to_instant(&*tv) };
+		let id = {
+			let mut guard = registry.lock().unwrap();
+			let id = guard.fresh_id();
+			guard.time.insert(id, TimeSource { cb, userdata, task: None });
+			id
+		};
+		let handle = Box::into_raw(Box::new(TimeHandle { id, registry: Arc::clone(&registry) }));
+		let event = handle as *mut TimeEventInternal;
+		spawn_time_task(&registry, id, event, at);
+		event
+	}
+
+	pub(super) extern "C" fn time_restart(e: *mut TimeEventInternal, tv: *const timeval) {
+		// SAFETY: see `io_enable`.
+		let handle = unsafe { &*(e as *const TimeHandle) };
+		// SAFETY: see `time_new`.
+		let at = unsafe { timeval_to_instant(&*tv) };
+		// A restart cancels the pending firing and reschedules fresh,
+		// mirroring `pa_mainloop_api::time_restart`'s "one-shot, re-armed"
+		// semantics.
+		if let Some(source) = handle.registry.lock().unwrap().time.get_mut(&handle.id) {
+			source.task = None;
+		}
+		spawn_time_task(&handle.registry, handle.id, e, at);
+	}
+
+	pub(super) extern "C" fn time_free(e: *mut TimeEventInternal) {
+		// SAFETY: see `io_free`.
+		let handle = unsafe { Box::from_raw(e as *mut TimeHandle) };
+		handle.registry.lock().unwrap().time.remove(&handle.id);
+	}
+
+	pub(super) extern "C" fn defer_new(
+		api: *const MainloopApi,
+		cb: Option<DeferEventCb>,
+		userdata: *mut c_void,
+	) -> *mut DeferEventInternal {
+		let Some(cb) = cb else { return std::ptr::null_mut() };
+		// SAFETY: see `io_new`.
+		let registry = unsafe { registry_from_api(api) };
+		let id = {
+			let mut guard = registry.lock().unwrap();
+			let id = guard.fresh_id();
+			guard.defer.insert(id, DeferSource { cb, userdata, enabled: true, task: None });
+			id
+		};
+		let handle = Box::into_raw(Box::new(DeferHandle { id, registry: Arc::clone(&registry) }));
+		let event = handle as *mut DeferEventInternal;
+		spawn_defer_task(&registry, id, event);
+		event
+	}
+
+	pub(super) extern "C" fn defer_enable(e: *mut DeferEventInternal, b: i32) {
+		// SAFETY: see `io_enable`.
+		let handle = unsafe { &*(e as *const DeferHandle) };
+		if let Some(source) = handle.registry.lock().unwrap().defer.get_mut(&handle.id) {
+			source.enabled = b != 0;
+		}
+	}
+
+	pub(super) extern "C" fn defer_free(e: *mut DeferEventInternal) {
+		// SAFETY: see `io_free`.
+		let handle = unsafe { Box::from_raw(e as *mut DeferHandle) };
+		handle.registry.lock().unwrap().defer.remove(&handle.id);
+	}
+
+	pub(super) extern "C" fn quit(api: *const MainloopApi, _retval: RetvalActual) {
+		// We have no run loop to stop; dropping every registered source is
+		// the closest equivalent, and matches `io_free`/`time_free`'s
+		// "cancel without blocking" contract. The boxed event handles
+		// themselves are reclaimed later, whenever PulseAudio calls the
+		// matching `*_free`.
+		// SAFETY: see `io_new`.
+		let registry = unsafe { registry_from_api(api) };
+		let mut guard = registry.lock().unwrap();
+		guard.io.clear();
+		guard.time.clear();
+		guard.defer.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn timeval_to_instant_in_the_future_is_roughly_now_plus_remaining() {
+		let target = SystemTime::now() + Duration::from_secs(5);
+		let since_epoch = target.duration_since(UNIX_EPOCH).unwrap();
+		let tv = timeval { tv_sec: since_epoch.as_secs() as _, tv_usec: since_epoch.subsec_micros() as _ };
+
+		let before = Instant::now();
+		let got = timeval_to_instant(&tv);
+		let after = Instant::now();
+
+		assert!(got >= before + Duration::from_secs(4));
+		assert!(got <= after + Duration::from_secs(5));
+	}
+
+	#[test]
+	fn timeval_to_instant_in_the_past_fires_immediately() {
+		let tv = timeval { tv_sec: 0, tv_usec: 0 };
+
+		let before = Instant::now();
+		let got = timeval_to_instant(&tv);
+		let after = Instant::now();
+
+		assert!((before..=after).contains(&got));
+	}
+
+	#[test]
+	fn timeval_to_instant_negative_fields_are_clamped_to_the_epoch() {
+		let tv = timeval { tv_sec: -1, tv_usec: -1 };
+
+		let before = Instant::now();
+		let got = timeval_to_instant(&tv);
+		let after = Instant::now();
+
+		assert!((before..=after).contains(&got));
+	}
+}
@@ -1,10 +1,13 @@
-///! Asyncronous operations
+//! Asyncronous operations
 use std::{
+	fmt,
 	future::Future,
 	pin::Pin,
 	task::{Context, Poll},
+	time::Duration,
 };
 
+use async_io::Timer;
 use libpulse_binding::error::Code;
 use pulse::{error::PAErr, operation::State};
 
@@ -12,6 +15,31 @@ use pulse::{error::PAErr, operation::State};
 /// pulseaudio server.
 pub struct Operation<F: ?Sized>(pulse::operation::Operation<F>);
 
+impl<F: ?Sized> Operation<F> {
+	/// Cancels the operation.
+	///
+	/// This will not necessarily cancel the work on the server side, but
+	/// will make sure the callback associated with this operation will not
+	/// be called anymore, effectively disabling the operation from the
+	/// client side's perspective.
+	pub fn cancel(&mut self) {
+		self.0.cancel();
+	}
+
+	/// Races this operation against a timer, cancelling it if it hasn't
+	/// completed by the time `timeout` elapses.
+	///
+	/// Useful for operations the server is not guaranteed to ever respond
+	/// to, such as telling the daemon to exit, since the daemon is likely to
+	/// exit before a success notification can be sent back.
+	/// [`Context::exit_daemon`][crate::context::Context::exit_daemon] awaits
+	/// its operation internally and returns a plain future rather than an
+	/// `Operation`, so `with_timeout` cannot be applied to it.
+	pub fn with_timeout(self, timeout: Duration) -> WithTimeout<F> {
+		WithTimeout { operation: self, timer: Timer::after(timeout) }
+	}
+}
+
 impl<F: ?Sized> Future for Operation<F> {
 	type Output = Result<(), PAErr>;
 
@@ -29,8 +57,63 @@ impl<F: ?Sized> Future for Operation<F> {
 	}
 }
 
+impl<F: ?Sized> Drop for Operation<F> {
+	fn drop(&mut self) {
+		// Cancelling an already-finished operation is a harmless no-op, so
+		// there's no need to check the state first. Without this, a
+		// dropped-before-completion operation would leave its callback
+		// registered, letting the server call back into state we've
+		// already freed.
+		self.0.cancel();
+	}
+}
+
 impl<F: ?Sized> From<pulse::operation::Operation<F>> for Operation<F> {
 	fn from(op: pulse::operation::Operation<F>) -> Self {
 		Operation(op)
 	}
 }
+
+/// Error returned by [`Operation::with_timeout`] when the operation didn't
+/// complete before the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl fmt::Display for Elapsed {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("operation timed out")
+	}
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Future returned by [`Operation::with_timeout`].
+pub struct WithTimeout<F: ?Sized> {
+	operation: Operation<F>,
+	timer: Timer,
+}
+
+impl<F: ?Sized> Future for WithTimeout<F> {
+	type Output = Result<Result<(), PAErr>, Elapsed>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		if let Poll::Ready(result) = Pin::new(&mut self.operation).poll(cx) {
+			return Poll::Ready(Ok(result));
+		}
+		if Pin::new(&mut self.timer).poll(cx).is_ready() {
+			self.operation.cancel();
+			return Poll::Ready(Err(Elapsed(())));
+		}
+		Poll::Pending
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn elapsed_display_message() {
+		assert_eq!(Elapsed(()).to_string(), "operation timed out");
+	}
+}
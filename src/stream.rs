@@ -0,0 +1,237 @@
+//! Async playback/record streams.
+//!
+//! Wraps `pulse::stream::Stream` and exposes it through the standard
+//! `futures` [`AsyncRead`]/[`AsyncWrite`] traits, so audio can be piped
+//! with ordinary `read`/`write`/`copy` loops instead of juggling
+//! write-request and read callbacks directly.
+
+use std::{
+	collections::VecDeque,
+	pin::Pin,
+	task::{Context as TaskContext, Poll},
+};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use libpulse_binding::{
+	error::{Code, PAErr},
+	sample::Spec,
+	stream::{FlagSet, PeekResult, SeekMode, State},
+};
+
+use crate::context::Context;
+
+struct ConnectFuture<'a> {
+	stream: &'a mut pulse::stream::Stream,
+}
+
+impl<'a> std::future::Future for ConnectFuture<'a> {
+	type Output = Result<(), PAErr>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+		match self.stream.get_state() {
+			State::Ready => return Poll::Ready(Ok(())),
+			// `Stream` has no errno-equivalent of its own to report here;
+			// the connection's actual failure reason lives on the owning
+			// `Context`, so this just reports the generic code PulseAudio
+			// uses for a dead connection.
+			State::Failed | State::Terminated => {
+				return Poll::Ready(Err(PAErr::from(Code::ConnectionTerminated)))
+			}
+			_ => (),
+		}
+		let waker = cx.waker().clone();
+		self.get_mut().stream.set_state_callback(Some(Box::new(move || {
+			waker.wake_by_ref();
+		})));
+		Poll::Pending
+	}
+}
+
+/// An async wrapper around a PulseAudio playback or record stream.
+///
+/// Connect it with [`connect_playback`][Stream::connect_playback] or
+/// [`connect_record`][Stream::connect_record], then use the
+/// [`AsyncWrite`]/[`AsyncRead`] implementations to move audio data once the
+/// stream is `Ready`. Writing or reading before the stream is ready is not
+/// an error, it just reports no progress (`Poll::Pending`) until the
+/// connection completes.
+pub struct Stream {
+	inner: pulse::stream::Stream,
+	/// Data peeked out of `inner` but not yet handed to a reader. PulseAudio
+	/// only lets us discard a peeked fragment in its entirety, so a read
+	/// into a buffer smaller than the current fragment has to stash the
+	/// remainder here rather than re-peeking it (which would hand out the
+	/// same bytes twice).
+	read_buffer: VecDeque<u8>,
+	/// The server's preferred block size for this stream's sample spec, from
+	/// [`Context::get_tile_size`]. `None` if the server didn't report one;
+	/// used to size `read_buffer` and to cap how much `poll_write` hands to
+	/// PulseAudio in one call.
+	tile_size: Option<usize>,
+}
+
+impl Stream {
+	/// Creates a new, unconnected stream on `context`.
+	///
+	/// `name` is a description of the stream's purpose, shown to the user
+	/// by tools like `pavucontrol`. `sample_spec` and `channel_map`
+	/// describe the format the stream will carry; if `channel_map` is
+	/// `None`, PulseAudio derives a default mapping from the sample spec's
+	/// channel count.
+	pub fn new(
+		context: &mut Context,
+		name: &str,
+		sample_spec: &Spec,
+		channel_map: Option<&pulse::channelmap::Map>,
+	) -> Option<Stream> {
+		let tile_size = context.get_tile_size(Some(sample_spec));
+		pulse::stream::Stream::new(context.raw_mut(), name, sample_spec, channel_map)
+			.map(|inner| Stream { inner, read_buffer: VecDeque::with_capacity(tile_size.unwrap_or(0)), tile_size })
+	}
+
+	/// Connects the stream for playback on the given device, or the
+	/// default sink if `device` is `None`.
+	pub async fn connect_playback(
+		&mut self,
+		device: Option<&str>,
+		flags: FlagSet,
+	) -> Result<(), PAErr> {
+		self.inner.connect_playback(device, None, flags, None, None)?;
+		ConnectFuture { stream: &mut self.inner }.await
+	}
+
+	/// Connects the stream for recording on the given device, or the
+	/// default source if `device` is `None`.
+	pub async fn connect_record(&mut self, device: Option<&str>, flags: FlagSet) -> Result<(), PAErr> {
+		self.inner.connect_record(device, None, flags)?;
+		ConnectFuture { stream: &mut self.inner }.await
+	}
+
+	/// Gets the current stream state.
+	pub fn get_state(&self) -> State {
+		self.inner.get_state()
+	}
+
+	/// Disconnects the stream.
+	pub fn disconnect(&mut self) -> Result<(), PAErr> {
+		self.inner.disconnect()
+	}
+
+	/// Sets the callback invoked when the playback buffer underflows, i.e.
+	/// the server ran out of data to play. Playback streams only.
+	pub fn set_underflow_callback(&mut self, callback: impl FnMut() + 'static) {
+		self.inner.set_underflow_callback(Some(Box::new(callback)));
+	}
+
+	/// Sets the callback invoked when the playback buffer overflows.
+	/// Playback streams only.
+	pub fn set_overflow_callback(&mut self, callback: impl FnMut() + 'static) {
+		self.inner.set_overflow_callback(Some(Box::new(callback)));
+	}
+}
+
+impl AsyncWrite for Stream {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &[u8],
+	) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		let writable = this.inner.writable_size().unwrap_or(0);
+		if writable == 0 {
+			let waker = cx.waker().clone();
+			this.inner.set_write_callback(Some(Box::new(move |_writable_size| {
+				waker.wake_by_ref();
+			})));
+			return Poll::Pending;
+		}
+
+		let mut to_write = writable.min(buf.len());
+		// Cap each write to the server's preferred block size rather than
+		// handing over everything `writable_size` allows at once; this
+		// keeps individual writes close to what the server already buffers
+		// in, instead of one oversized copy followed by a long stall.
+		if let Some(tile_size) = this.tile_size {
+			to_write = to_write.min(tile_size);
+		}
+		// `write` copies `to_write` bytes of `buf` into PulseAudio's
+		// internal buffer and commits them at the current write index;
+		// the two-step begin_write/write dance PulseAudio offers for
+		// zero-copy writes isn't exposed, as a single bounded copy here is
+		// simpler and the cost is already paid by the `buf` -> kernel
+		// socket copy downstream.
+		match this.inner.write(&buf[..to_write], None, 0, SeekMode::Relative) {
+			Ok(()) => Poll::Ready(Ok(to_write)),
+			Err(err) => Poll::Ready(Err(std::io::Error::other(err))),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_close(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+		Poll::Ready(
+			self.get_mut()
+				.inner
+				.disconnect()
+				.map_err(std::io::Error::other),
+		)
+	}
+}
+
+impl AsyncRead for Stream {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut TaskContext<'_>,
+		buf: &mut [u8],
+	) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		// `peek` fails on a stream that hasn't finished connecting; wait for
+		// data to arrive instead, as `poll_write` does for write space.
+		if matches!(this.inner.get_state(), State::Unconnected | State::Creating) {
+			let waker = cx.waker().clone();
+			this.inner.set_read_callback(Some(Box::new(move |_readable_size| {
+				waker.wake_by_ref();
+			})));
+			return Poll::Pending;
+		}
+		while this.read_buffer.is_empty() {
+			match this.inner.peek() {
+				Ok(PeekResult::Empty) => {
+					let waker = cx.waker().clone();
+					this.inner.set_read_callback(Some(Box::new(move |_readable_size| {
+						waker.wake_by_ref();
+					})));
+					return Poll::Pending;
+				}
+				// A hole in the stream (e.g. after an overrun on the
+				// server side) carries no data of its own; discard it and
+				// look at what comes next.
+				Ok(PeekResult::Hole(_)) => {
+					if let Err(err) = this.inner.discard() {
+						return Poll::Ready(Err(std::io::Error::other(err)));
+					}
+				}
+				Ok(PeekResult::Data(data)) => {
+					// The whole fragment has to be drained into
+					// `read_buffer` before we can discard it: PulseAudio
+					// only lets us discard a peeked fragment in full, and
+					// re-peeking without discarding just hands back the
+					// same bytes again.
+					this.read_buffer.extend(data.iter().copied());
+					if let Err(err) = this.inner.discard() {
+						return Poll::Ready(Err(std::io::Error::other(err)));
+					}
+				}
+				Err(err) => return Poll::Ready(Err(std::io::Error::other(err))),
+			}
+		}
+
+		let to_copy = this.read_buffer.len().min(buf.len());
+		for (dst, src) in buf[..to_copy].iter_mut().zip(this.read_buffer.drain(..to_copy)) {
+			*dst = src;
+		}
+		Poll::Ready(Ok(to_copy))
+	}
+}